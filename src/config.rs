@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use crate::dns::DnsConfig;
+use crate::metrics::MetricsConfig;
+use crate::policy::MacRule;
+
+const CONFIG_PATH_ENV: &str = "DHCP_NDP_BEACON_CONFIG";
+
+/// Runtime configuration, loaded from a YAML file so deployments on
+/// different hosts don't require a recompile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub leases_path: String,
+    pub bind_addr: String,
+    pub ndp: NdpCommandConfig,
+    /// How often, in seconds, to refresh the cached status snapshot.
+    /// `None` disables the background poller.
+    pub period: Option<f64>,
+    /// Allow-listed devices, keyed by MAC address. `None` means the device
+    /// is expected but its attributes aren't checked.
+    pub mac_rules: HashMap<String, Option<MacRule>>,
+    /// Optional DNS cross-check of lease hostnames/IPs against a resolver.
+    pub dns: DnsConfig,
+    pub metrics: MetricsConfig,
+}
+
+/// The external `ndp` binary and the arguments used to list the cache.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NdpCommandConfig {
+    pub bin: String,
+    pub args: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            leases_path: "/var/db/dhcpd/dhcpd.leases".to_string(),
+            bind_addr: "192.168.0.1:80".to_string(),
+            ndp: NdpCommandConfig::default(),
+            period: Some(10.0),
+            mac_rules: HashMap::new(),
+            dns: DnsConfig::default(),
+            metrics: MetricsConfig::default(),
+        }
+    }
+}
+
+impl Default for NdpCommandConfig {
+    fn default() -> Self {
+        Self {
+            bin: "ndp".to_string(),
+            args: vec!["-a".to_string()],
+        }
+    }
+}
+
+impl Config {
+    /// Loads the config from the path given as the first CLI argument, or
+    /// from `DHCP_NDP_BEACON_CONFIG`, falling back to defaults if neither is set.
+    pub fn load_from_env() -> Self {
+        let path = env::args().nth(1).or_else(|| env::var(CONFIG_PATH_ENV).ok());
+        let mut config = match path {
+            Some(path) => Self::load_from_file(&path)
+                .unwrap_or_else(|err| panic!("failed to load config from {path}: {err}")),
+            None => Self::default(),
+        };
+        config.normalize();
+        config.validate().unwrap_or_else(|err| panic!("invalid config: {err}"));
+        config
+    }
+
+    /// Lowercases `mac_rules` keys so they match MAC addresses parsed from
+    /// lease files and `ndp -a`, which are normalized the same way.
+    fn normalize(&mut self) {
+        self.mac_rules = self.mac_rules.drain()
+            .map(|(mac, rule)| (crate::normalize_mac(&mac), rule))
+            .collect();
+    }
+
+    fn load_from_file(path: &str) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        serde_yaml::from_str(&contents).map_err(|err| err.to_string())
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some(period) = self.period {
+            if period.is_nan() || period <= 0.0 {
+                return Err(format!("period must be greater than 0 seconds, got {period}"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::TokioAsyncResolver;
+
+/// How to reach the resolver used to cross-check lease hostnames and IPs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DnsConfig {
+    pub enabled: bool,
+    /// `ip:port` of the resolver to use (an IP literal, not a hostname); falls back
+    /// to the system resolver when unset.
+    pub server: Option<String>,
+    pub timeout_secs: f64,
+    /// Per-hostname overrides, skipping the forward lookup with a fixed IP.
+    pub overrides: HashMap<String, IpAddr>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server: None,
+            timeout_secs: 2.0,
+            overrides: HashMap::new(),
+        }
+    }
+}
+
+/// Forward/reverse DNS cross-check result for a single merged entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DnsCheck {
+    pub forward_ok: bool,
+    pub reverse_ok: bool,
+    pub resolved_ip: Option<IpAddr>,
+    pub resolved_name: Option<String>,
+}
+
+pub struct DnsVerifier {
+    resolver: TokioAsyncResolver,
+    timeout: Duration,
+    overrides: HashMap<String, IpAddr>,
+}
+
+impl DnsVerifier {
+    pub fn new(config: &DnsConfig) -> Self {
+        let resolver_config = match &config.server {
+            Some(server) => {
+                let addr: std::net::SocketAddr =
+                    server.parse().expect("invalid dns.server address");
+                ResolverConfig::from_parts(
+                    None,
+                    Vec::new(),
+                    NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port(), true),
+                )
+            }
+            None => ResolverConfig::default(),
+        };
+        let resolver = TokioAsyncResolver::tokio(resolver_config, ResolverOpts::default());
+        Self {
+            resolver,
+            timeout: Duration::from_secs_f64(config.timeout_secs),
+            overrides: config.overrides.clone(),
+        }
+    }
+
+    /// Checks that `hostname` resolves to `ip_address` and that `ip_address` resolves
+    /// back to a name, timing each lookup out so a slow resolver can't stall the poll.
+    pub async fn check(&self, hostname: Option<&str>, ip_address: IpAddr) -> DnsCheck {
+        let resolved_ip = match hostname {
+            Some(hostname) => match self.overrides.get(hostname) {
+                Some(ip) => Some(*ip),
+                None => self.forward_lookup(hostname).await,
+            },
+            None => None,
+        };
+        let resolved_name = self.reverse_lookup(ip_address).await;
+
+        DnsCheck {
+            forward_ok: hostname.is_some() && resolved_ip == Some(ip_address),
+            reverse_ok: resolved_name.is_some(),
+            resolved_ip,
+            resolved_name,
+        }
+    }
+
+    async fn forward_lookup(&self, hostname: &str) -> Option<IpAddr> {
+        let lookup = tokio::time::timeout(self.timeout, self.resolver.lookup_ip(hostname))
+            .await
+            .ok()?
+            .ok()?;
+        lookup.iter().next()
+    }
+
+    async fn reverse_lookup(&self, ip_address: IpAddr) -> Option<String> {
+        let lookup = tokio::time::timeout(self.timeout, self.resolver.reverse_lookup(ip_address))
+            .await
+            .ok()?
+            .ok()?;
+        lookup.iter().next().map(|name| name.to_string())
+    }
+}
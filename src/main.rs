@@ -1,66 +1,188 @@
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::net::Ipv4Addr;
+use std::net::{IpAddr, Ipv4Addr};
 use std::process::Command;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
-use actix_web::{App, get, HttpResponse, HttpServer, Responder};
+use actix_web::{App, get, HttpResponse, HttpServer, Responder, web};
 use chrono::{NaiveDateTime, Local, DateTime, TimeZone};
+use futures::future::join_all;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
 
-const DHCP_LEASES_FILE_PATH: &str = "/var/db/dhcpd/dhcpd.leases";
+mod config;
+mod dns;
+mod metrics;
+mod policy;
+
+use config::Config;
+use dns::{DnsCheck, DnsVerifier};
+use policy::{matches_rule, DeviceStatus};
+
+/// A `MergedEntry` map together with the time it was generated, refreshed
+/// periodically by the background poller instead of on every request.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct StatusSnapshot {
+    generated_at: String,
+    pub(crate) entries: BTreeMap<String, MergedEntry>,
+}
 
 #[get("/api/status")]
-async fn index() -> impl Responder {
+async fn index(status: web::Data<RwLock<StatusSnapshot>>) -> impl Responder {
+    HttpResponse::Ok().json(&*status.read().await)
+}
+
+#[get("/api/alerts")]
+async fn alerts(status: web::Data<RwLock<StatusSnapshot>>) -> impl Responder {
+    let status = status.read().await;
+    let alerts: BTreeMap<_, _> = status.entries.iter()
+        .filter(|(_, entry)| entry.status != DeviceStatus::Known)
+        .map(|(mac, entry)| (mac.clone(), entry.clone()))
+        .collect();
+    HttpResponse::Ok().json(alerts)
+}
+
+#[get("/metrics")]
+async fn metrics_endpoint(
+    status: web::Data<RwLock<StatusSnapshot>>,
+    config: web::Data<Config>,
+) -> impl Responder {
+    let status = status.read().await;
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render(&status, &config.metrics))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(crate) struct MergedEntry {
+    pub(crate) ndp_entries: Vec<NdpEntry>,
+    pub(crate) dhcp_lease: Option<DhcpLease>,
+    status: DeviceStatus,
+    dns: Option<DnsCheck>,
+}
+
+/// Lowercases a MAC address so lookups and merges aren't sensitive to the
+/// case a lease file, `ndp -a`, or an operator's config happens to use.
+pub(crate) fn normalize_mac(mac_address: &str) -> String {
+    mac_address.to_lowercase()
+}
+
+fn device_status(mac_address: &str, lease: Option<&DhcpLease>, config: &Config) -> DeviceStatus {
+    let rule = match config.mac_rules.get(&normalize_mac(mac_address)) {
+        Some(rule) => rule,
+        None => return DeviceStatus::Unknown,
+    };
+    let rule = match rule {
+        Some(rule) => rule,
+        None => return DeviceStatus::Known,
+    };
+    let matches = matches_rule(
+        rule,
+        lease.and_then(|lease| lease.hostname.as_deref()),
+        lease.and_then(|lease| lease.vendor_class.as_deref()),
+        lease.and_then(|lease| lease.uid.as_deref()),
+    );
+    if matches { DeviceStatus::Known } else { DeviceStatus::Mismatch }
+}
+
+async fn refresh_status(config: &Config, dns_verifier: Option<&DnsVerifier>) -> StatusSnapshot {
     // distinct by mac address and exclude unavailable lease
     let mut leases = BTreeMap::<String, DhcpLease>::new();
-    for lease in read_dhcp_leases().await {
+    for lease in read_dhcp_leases(config).await {
         if !lease.is_available() {
             continue;
         }
         leases.insert(lease.mac_address.to_string(), lease);
     }
 
-    let mut response = BTreeMap::<String, MergedEntry>::new();
+    let mut entries = BTreeMap::<String, MergedEntry>::new();
     // Insert leases
     for lease in leases.values() {
-        response.insert(lease.mac_address.to_string(), MergedEntry {
+        entries.insert(lease.mac_address.to_string(), MergedEntry {
+            status: device_status(&lease.mac_address, Some(lease), config),
             dhcp_lease: Some(lease.clone()),
             ndp_entries: Vec::new(),
+            dns: None,
         });
     }
     // Inset ndp entries
-    for entry in retrieve_ndp_entries().await {
-        response
+    for entry in retrieve_ndp_entries(config).await {
+        entries
             .entry(entry.mac_address.to_string())
             .or_insert_with(|| MergedEntry {
+                status: device_status(&entry.mac_address, None, config),
                 ndp_entries: Vec::new(),
                 dhcp_lease: None,
+                dns: None,
             })
             .ndp_entries.push(entry.clone());
     }
-    HttpResponse::Ok().json(response)
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MergedEntry {
-    ndp_entries: Vec<NdpEntry>,
-    dhcp_lease: Option<DhcpLease>,
+    if let Some(verifier) = dns_verifier {
+        let checks = join_all(entries.iter().filter_map(|(mac, entry)| {
+            let lease = entry.dhcp_lease.as_ref()?;
+            let mac = mac.clone();
+            let hostname = lease.hostname.clone();
+            let ip_address = IpAddr::V4(lease.ip_address);
+            let verifier = &verifier;
+            Some(async move { (mac, verifier.check(hostname.as_deref(), ip_address).await) })
+        })).await;
+        for (mac, check) in checks {
+            if let Some(entry) = entries.get_mut(&mac) {
+                entry.dns = Some(check);
+            }
+        }
+    }
+
+    StatusSnapshot {
+        generated_at: Local::now().to_rfc3339(),
+        entries,
+    }
 }
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     println!("Starting");
-    HttpServer::new(|| App::new().service(index))
-        .bind("192.168.0.1:80")?
+    let config = Config::load_from_env();
+    let bind_addr = config.bind_addr.clone();
+    let dns_verifier = config.dns.enabled.then(|| Arc::new(DnsVerifier::new(&config.dns)));
+
+    // Run the first poll eagerly so the server never serves an empty snapshot.
+    let status = Arc::new(RwLock::new(refresh_status(&config, dns_verifier.as_deref()).await));
+
+    if let Some(period) = config.period {
+        let config = config.clone();
+        let status = status.clone();
+        let dns_verifier = dns_verifier.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs_f64(period));
+            loop {
+                interval.tick().await;
+                let snapshot = refresh_status(&config, dns_verifier.as_deref()).await;
+                *status.write().await = snapshot;
+            }
+        });
+    }
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(config.clone()))
+            .app_data(web::Data::from(status.clone()))
+            .service(index)
+            .service(alerts)
+            .service(metrics_endpoint)
+    })
+        .bind(bind_addr)?
         .run()
         .await
 }
 
-async fn read_dhcp_leases() -> Vec<DhcpLease> {
-    let leases_file = File::open(DHCP_LEASES_FILE_PATH).unwrap();
+async fn read_dhcp_leases(config: &Config) -> Vec<DhcpLease> {
+    let leases_file = File::open(&config.leases_path).unwrap();
     let mut leases: Vec<DhcpLease> = Vec::new();
     let mut current_buf = String::new();
     let mut in_section = false;
@@ -72,6 +194,7 @@ async fn read_dhcp_leases() -> Vec<DhcpLease> {
         }
         if in_section {
             current_buf.push_str(line);
+            current_buf.push('\n');
         }
         if line.starts_with("}") {
             leases.push(DhcpLease::from_str(&current_buf).unwrap());
@@ -82,9 +205,9 @@ async fn read_dhcp_leases() -> Vec<DhcpLease> {
     leases
 }
 
-async fn retrieve_ndp_entries() -> Vec<NdpEntry> {
-    let stdout = Command::new("ndp")
-        .arg("-a")
+async fn retrieve_ndp_entries(config: &Config) -> Vec<NdpEntry> {
+    let stdout = Command::new(&config.ndp.bin)
+        .args(&config.ndp.args)
         .output()
         .unwrap()
         .stdout;
@@ -99,52 +222,118 @@ async fn retrieve_ndp_entries() -> Vec<NdpEntry> {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct DhcpLease {
-    mac_address: String,
-    ip_address: Ipv4Addr,
-    expire_at: String,
-    hostname: Option<String>,
+pub(crate) struct DhcpLease {
+    pub(crate) mac_address: String,
+    pub(crate) ip_address: Ipv4Addr,
+    starts_at: String,
+    pub(crate) expire_at: String,
+    pub(crate) hostname: Option<String>,
+    binding_state: BindingState,
+    uid: Option<String>,
+    vendor_class: Option<String>,
 }
 
 impl DhcpLease {
     fn is_available(&self) -> bool {
         let expire_at = DateTime::parse_from_rfc3339(&self.expire_at).unwrap();
-        expire_at > Local::now()
+        self.binding_state == BindingState::Active && expire_at > Local::now()
     }
 }
 
+/// Parses a `%Y/%m/%d %H:%M:%S` UTC timestamp, as used for `starts`/`ends`, into RFC 3339.
+fn parse_lease_timestamp(value: &str) -> String {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y/%m/%d %H:%M:%S").unwrap();
+    Local.from_utc_datetime(&naive).to_rfc3339()
+}
+
 impl FromStr for DhcpLease {
     type Err = &'static str;
 
     /// Must be trimmed
     fn from_str(value: &str) -> Result<Self, Self::Err> {
         let address_regex = Regex::new(r"lease (.*?) \{").unwrap();
-        let end_regex = Regex::new(r"ends . (.*?);").unwrap();
+        let starts_regex = Regex::new(r"(?m)^\s*starts \d+ (.*?);").unwrap();
+        let end_regex = Regex::new(r"(?m)^\s*ends \d+ (.*?);").unwrap();
         let mac_address_regex = Regex::new(r"hardware ethernet (.*?);").unwrap();
         let host_regex = Regex::new("client-hostname \"(.*?)\";").unwrap();
+        let binding_state_regex = Regex::new(r"(?m)^\s*binding state (\w+);").unwrap();
+        let uid_regex = Regex::new("(?m)^\\s*uid \"(.*?)\";").unwrap();
+        let vendor_class_regex =
+            Regex::new("(?m)^\\s*set vendor-class-identifier = \"(.*?)\";").unwrap();
 
         let ip_address =
             Ipv4Addr::from_str(
-                &address_regex.captures_iter(&value).next().unwrap()[1]
+                &address_regex.captures_iter(value).next().unwrap()[1]
             ).unwrap();
-        let expire_at = end_regex.captures_iter(&value).next().unwrap()[1].to_string();
-        let expire_at = NaiveDateTime::parse_from_str(&expire_at, "%Y/%m/%d %H:%M:%S").unwrap();
-        let expire_at = Local.from_utc_datetime(&expire_at).to_rfc3339();
-        let mac_address = mac_address_regex
-            .captures_iter(&value).next().unwrap()[1].to_string();
+        let starts_at = starts_regex.captures_iter(value).next()
+            .map(|cap| parse_lease_timestamp(&cap[1]))
+            .unwrap();
+        let expire_at = end_regex.captures_iter(value).next()
+            .map(|cap| parse_lease_timestamp(&cap[1]))
+            .unwrap();
+        let mac_address = normalize_mac(
+            &mac_address_regex.captures_iter(value).next().unwrap()[1]
+        );
         let hostname = host_regex
-            .captures_iter(&value).next().map(|cap| cap[1].to_string());
+            .captures_iter(value).next().map(|cap| cap[1].to_string());
+        let binding_state = binding_state_regex
+            .captures_iter(value).next()
+            .map(|cap| BindingState::from_str(&cap[1]).unwrap())
+            .unwrap_or(BindingState::Free);
+        let uid = uid_regex
+            .captures_iter(value).next().map(|cap| cap[1].to_string());
+        let vendor_class = vendor_class_regex
+            .captures_iter(value).next().map(|cap| cap[1].to_string());
         Ok(Self {
             mac_address,
             hostname,
             ip_address,
+            starts_at,
             expire_at,
+            binding_state,
+            uid,
+            vendor_class,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+enum BindingState {
+    Active,
+    Free,
+    Backup,
+    Abandoned,
+    Expired,
+    Released,
+    Reset,
+    Bootp,
+    Reserved,
+    /// Any keyword the ISC grammar doesn't define yet, kept verbatim so
+    /// parsing never panics on an unrecognized `binding state`.
+    Other(String),
+}
+
+impl FromStr for BindingState {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "active" => Self::Active,
+            "free" => Self::Free,
+            "backup" => Self::Backup,
+            "abandoned" => Self::Abandoned,
+            "expired" => Self::Expired,
+            "released" => Self::Released,
+            "reset" => Self::Reset,
+            "bootp" => Self::Bootp,
+            "reserved" => Self::Reserved,
+            other => Self::Other(other.to_string()),
         })
     }
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
-enum NdpCacheState {
+pub(crate) enum NdpCacheState {
     NoState,
     WaitDelete,
     Incomplete,
@@ -189,10 +378,10 @@ impl FromStr for NdpCacheState {
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct NdpEntry {
-    mac_address: String,
-    ip_address: String,
-    cache_state: NdpCacheState,
+pub(crate) struct NdpEntry {
+    pub(crate) mac_address: String,
+    pub(crate) ip_address: String,
+    pub(crate) cache_state: NdpCacheState,
 }
 
 impl FromStr for NdpEntry {
@@ -205,7 +394,7 @@ impl FromStr for NdpEntry {
         let regex = Regex::new(r"([^ ]+)").unwrap();
         let mut matches = regex.captures_iter(s);
         let ip_address = matches.next().unwrap()[1].to_string();
-        let mac_address = matches.next().unwrap()[1].to_string();
+        let mac_address = normalize_mac(&matches.next().unwrap()[1]);
         let cache_state =
             NdpCacheState::from_str(&matches.skip(2).next().unwrap()[1]).unwrap();
         Ok(NdpEntry {
@@ -214,4 +403,66 @@ impl FromStr for NdpEntry {
             cache_state,
         })
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_an_active_lease_with_all_fields() {
+        let lease = DhcpLease::from_str(
+            "lease 192.168.1.5 {\n\
+             starts 3 2024/01/10 10:00:00;\n\
+             ends 3 2024/01/10 22:00:00;\n\
+             binding state active;\n\
+             next binding state free;\n\
+             hardware ethernet AA:BB:CC:DD:EE:01;\n\
+             uid \"\\001\\252\\273\\314\\335\\356\\001\";\n\
+             client-hostname \"my-host\";\n\
+             set vendor-class-identifier = \"android-dhcp-7.1.2\";\n\
+             }\n",
+        ).unwrap();
+
+        assert_eq!(lease.mac_address, "aa:bb:cc:dd:ee:01");
+        assert_eq!(lease.ip_address, Ipv4Addr::new(192, 168, 1, 5));
+        assert_eq!(lease.hostname.as_deref(), Some("my-host"));
+        assert_eq!(lease.uid.as_deref(), Some("\\001\\252\\273\\314\\335\\356\\001"));
+        assert_eq!(lease.vendor_class.as_deref(), Some("android-dhcp-7.1.2"));
+        assert_eq!(lease.binding_state, BindingState::Active);
+    }
+
+    #[test]
+    fn an_expired_lease_is_not_available() {
+        let lease = DhcpLease::from_str(
+            "lease 192.168.1.6 {\n\
+             starts 3 2024/01/10 10:00:00;\n\
+             ends 3 2024/01/10 22:00:00;\n\
+             binding state expired;\n\
+             hardware ethernet aa:bb:cc:dd:ee:02;\n\
+             }\n",
+        ).unwrap();
+
+        assert_eq!(lease.binding_state, BindingState::Expired);
+        assert!(!lease.is_available());
+    }
+
+    #[test]
+    fn a_free_lease_has_no_optional_fields_and_ignores_the_next_binding_state_line() {
+        let lease = DhcpLease::from_str(
+            "lease 192.168.1.7 {\n\
+             starts 3 2024/01/10 10:00:00;\n\
+             ends 3 2024/01/10 22:00:00;\n\
+             binding state free;\n\
+             next binding state active;\n\
+             hardware ethernet aa:bb:cc:dd:ee:03;\n\
+             }\n",
+        ).unwrap();
+
+        assert_eq!(lease.binding_state, BindingState::Free);
+        assert_eq!(lease.hostname, None);
+        assert_eq!(lease.uid, None);
+        assert_eq!(lease.vendor_class, None);
+        assert!(!lease.is_available());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,104 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::{NdpCacheState, StatusSnapshot};
+
+/// Settings for the `/metrics` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Leases expiring within this many seconds count toward `dhcp_leases_expiring_soon`.
+    pub expiring_within_secs: f64,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            expiring_within_secs: 300.0,
+        }
+    }
+}
+
+/// Renders the merged status map as Prometheus text-format gauges.
+pub fn render(snapshot: &StatusSnapshot, config: &MetricsConfig) -> String {
+    let now = Local::now();
+    let expiring_before = now + chrono::Duration::seconds(config.expiring_within_secs as i64);
+
+    let mut active_leases = 0u64;
+    let mut expiring_soon = 0u64;
+    let mut ndp_state_counts = BTreeMap::<String, u64>::new();
+
+    let mut out = String::new();
+
+    writeln!(out, "# HELP dhcp_lease_info Present DHCP leases, one series per device.").unwrap();
+    writeln!(out, "# TYPE dhcp_lease_info gauge").unwrap();
+    for entry in snapshot.entries.values() {
+        let Some(lease) = &entry.dhcp_lease else { continue };
+        active_leases += 1;
+        if let Ok(expire_at) = DateTime::parse_from_rfc3339(&lease.expire_at) {
+            if expire_at <= expiring_before {
+                expiring_soon += 1;
+            }
+        }
+        writeln!(
+            out,
+            "dhcp_lease_info{{mac=\"{}\",ip=\"{}\",hostname=\"{}\"}} 1",
+            escape_label(&lease.mac_address),
+            escape_label(&lease.ip_address.to_string()),
+            escape_label(lease.hostname.as_deref().unwrap_or("")),
+        ).unwrap();
+    }
+
+    writeln!(out, "# HELP ndp_entry_info Present NDP neighbor cache entries, one series per device.").unwrap();
+    writeln!(out, "# TYPE ndp_entry_info gauge").unwrap();
+    for entry in snapshot.entries.values() {
+        for ndp_entry in &entry.ndp_entries {
+            let state = ndp_entry.cache_state.to_string();
+            *ndp_state_counts.entry(state.clone()).or_insert(0) += 1;
+            writeln!(
+                out,
+                "ndp_entry_info{{mac=\"{}\",ip=\"{}\",state=\"{}\"}} 1",
+                escape_label(&ndp_entry.mac_address),
+                escape_label(&ndp_entry.ip_address),
+                escape_label(&state),
+            ).unwrap();
+        }
+    }
+
+    writeln!(out, "# HELP dhcp_leases_active Number of active, non-expired DHCP leases.").unwrap();
+    writeln!(out, "# TYPE dhcp_leases_active gauge").unwrap();
+    writeln!(out, "dhcp_leases_active {active_leases}").unwrap();
+
+    writeln!(out, "# HELP dhcp_leases_expiring_soon Leases expiring within the configured window.").unwrap();
+    writeln!(out, "# TYPE dhcp_leases_expiring_soon gauge").unwrap();
+    writeln!(out, "dhcp_leases_expiring_soon {expiring_soon}").unwrap();
+
+    writeln!(out, "# HELP ndp_cache_state Number of NDP entries in each cache state.").unwrap();
+    writeln!(out, "# TYPE ndp_cache_state gauge").unwrap();
+    for state in all_ndp_cache_states() {
+        let count = ndp_state_counts.get(&state).copied().unwrap_or(0);
+        writeln!(out, "ndp_cache_state{{state=\"{}\"}} {count}", escape_label(&state)).unwrap();
+    }
+
+    out
+}
+
+fn all_ndp_cache_states() -> Vec<String> {
+    [
+        NdpCacheState::NoState,
+        NdpCacheState::WaitDelete,
+        NdpCacheState::Incomplete,
+        NdpCacheState::Reachable,
+        NdpCacheState::Stale,
+        NdpCacheState::Delay,
+        NdpCacheState::Probe,
+        NdpCacheState::Unknown,
+    ].iter().map(|state| state.to_string()).collect()
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
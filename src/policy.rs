@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+/// Attributes a device with a given MAC address is expected to present.
+/// Any field left unset is not checked.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacRule {
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+    /// Raw substrings that must appear somewhere in the lease's hostname,
+    /// vendor class, or uid.
+    pub contains: Vec<String>,
+}
+
+/// Whether a merged entry belongs to a device the operator expects to see,
+/// and whether it matches the rule recorded for its MAC address.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum DeviceStatus {
+    Known,
+    Unknown,
+    Mismatch,
+}
+
+/// Checks a lease's attributes against an allow-listed rule for its MAC address.
+pub fn matches_rule(rule: &MacRule, hostname: Option<&str>, vendor_class: Option<&str>, uid: Option<&str>) -> bool {
+    if let Some(expected) = &rule.hostname {
+        if hostname != Some(expected.as_str()) {
+            return false;
+        }
+    }
+    if let Some(expected) = &rule.vendor_class {
+        if vendor_class != Some(expected.as_str()) {
+            return false;
+        }
+    }
+    rule.contains.iter().all(|substring| {
+        [hostname, vendor_class, uid]
+            .into_iter()
+            .flatten()
+            .any(|field| field.contains(substring.as_str()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_rule_matches_anything() {
+        let rule = MacRule::default();
+        assert!(matches_rule(&rule, Some("host"), Some("vendor"), Some("uid")));
+        assert!(matches_rule(&rule, None, None, None));
+    }
+
+    #[test]
+    fn hostname_only_rule_checks_just_the_hostname() {
+        let rule = MacRule {
+            hostname: Some("my-host".to_string()),
+            ..MacRule::default()
+        };
+        assert!(matches_rule(&rule, Some("my-host"), Some("anything"), None));
+        assert!(!matches_rule(&rule, Some("other-host"), Some("anything"), None));
+        assert!(!matches_rule(&rule, None, Some("anything"), None));
+    }
+
+    #[test]
+    fn vendor_only_rule_checks_just_the_vendor_class() {
+        let rule = MacRule {
+            vendor_class: Some("android-dhcp-7.1.2".to_string()),
+            ..MacRule::default()
+        };
+        assert!(matches_rule(&rule, Some("any-host"), Some("android-dhcp-7.1.2"), None));
+        assert!(!matches_rule(&rule, Some("any-host"), Some("other-vendor"), None));
+        assert!(!matches_rule(&rule, Some("any-host"), None, None));
+    }
+
+    #[test]
+    fn contains_rule_requires_every_substring_to_appear_somewhere() {
+        let rule = MacRule {
+            contains: vec!["android".to_string(), "7.1".to_string()],
+            ..MacRule::default()
+        };
+        assert!(matches_rule(&rule, Some("phone"), Some("android-dhcp-7.1.2"), None));
+        assert!(!matches_rule(&rule, Some("phone"), Some("android-dhcp-8.0.0"), None));
+        assert!(matches_rule(&rule, None, Some("android-7.1"), None));
+    }
+}